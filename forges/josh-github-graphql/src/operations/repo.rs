@@ -27,6 +27,62 @@ pub struct RequiredStatusCheck {
     pub integration_id: Option<i64>,
 }
 
+/// A push was rejected because one or more required status checks have
+/// not been satisfied for the head being pushed.
+#[derive(Debug)]
+pub struct PushGateRejection {
+    pub missing_contexts: Vec<String>,
+}
+
+/// Returns true if `pattern` (a ruleset ref-name condition, which may be
+/// `~ALL`, `~DEFAULT_BRANCH`, or a `refs/heads/**`-style fnmatch glob)
+/// matches `target_ref`.
+fn ref_name_condition_matches(pattern: &str, target_ref: &str, default_branch_ref: &str) -> bool {
+    match pattern {
+        "~ALL" => true,
+        "~DEFAULT_BRANCH" => target_ref == default_branch_ref,
+        pattern => fnmatch(pattern, target_ref),
+    }
+}
+
+/// Returns true if `target` (a ruleset's `RepositoryRulesetTarget`) applies
+/// to the ref namespace `target_ref` falls in. A branch-scoped ruleset
+/// never governs a tag push and vice versa, regardless of how broad its
+/// `~ALL`/glob ref-name conditions are; rulesets with no branch/tag target
+/// (e.g. push rulesets) are not ref-namespace scoped and always match.
+fn ruleset_target_matches_namespace(
+    target: &Option<RepositoryRulesetTarget>,
+    target_ref: &str,
+) -> bool {
+    match target {
+        Some(RepositoryRulesetTarget::Branch) => target_ref.starts_with("refs/heads/"),
+        Some(RepositoryRulesetTarget::Tag) => target_ref.starts_with("refs/tags/"),
+        _ => true,
+    }
+}
+
+/// A minimal glob matcher supporting `*` (any run of characters other than
+/// `/`) and `**` (any run of characters, including `/`) -- the only
+/// wildcards GitHub's ruleset ref-name conditions use.
+fn fnmatch(pattern: &str, text: &str) -> bool {
+    fn inner(pat: &[u8], text: &[u8]) -> bool {
+        match pat.first() {
+            None => text.is_empty(),
+            Some(b'*') if pat.get(1) == Some(&b'*') => {
+                let rest = &pat[2..];
+                (0..=text.len()).any(|i| inner(rest, &text[i..]))
+            }
+            Some(b'*') => {
+                let rest = &pat[1..];
+                let boundary = text.iter().position(|&c| c == b'/').unwrap_or(text.len());
+                (0..=boundary).any(|i| inner(rest, &text[i..]))
+            }
+            Some(&c) => !text.is_empty() && text[0] == c && inner(&pat[1..], &text[1..]),
+        }
+    }
+    inner(pattern.as_bytes(), text.as_bytes())
+}
+
 impl GithubApiConnection {
     /// Returns (default_branch_name, default_branch_head_oid) if available.
     pub async fn get_default_branch(
@@ -57,30 +113,45 @@ impl GithubApiConnection {
         Ok(Some((default_ref.name, target.oid)))
     }
 
-    /// Returns all rulesets for the given repository with their branch conditions.
+    /// Returns all rulesets for the given repository with their branch conditions,
+    /// paginating through the rulesets connection until it is exhausted.
     pub async fn get_repository_rulesets(
         &self,
         owner: &str,
         name: &str,
     ) -> anyhow::Result<Vec<RepositoryRuleset>> {
-        let variables = get_repository_rulesets::Variables {
-            owner: owner.to_string(),
-            name: name.to_string(),
-        };
+        let mut nodes = vec![];
+        let mut after = None;
 
-        let response = self
-            .make_request::<GetRepositoryRulesets>(variables)
-            .await?;
+        loop {
+            let variables = get_repository_rulesets::Variables {
+                owner: owner.to_string(),
+                name: name.to_string(),
+                after,
+            };
 
-        let rulesets = response
-            .repository
-            .and_then(|r| r.rulesets)
-            .and_then(|r| r.nodes)
-            .unwrap_or_default();
+            let response = self
+                .make_request::<GetRepositoryRulesets>(variables)
+                .await?;
 
-        Ok(rulesets
+            let rulesets = match response.repository.and_then(|r| r.rulesets) {
+                Some(r) => r,
+                None => break,
+            };
+
+            nodes.extend(rulesets.nodes.unwrap_or_default().into_iter().flatten());
+
+            if !rulesets.page_info.has_next_page {
+                break;
+            }
+            after = match rulesets.page_info.end_cursor {
+                Some(cursor) => Some(cursor),
+                None => break,
+            };
+        }
+
+        Ok(nodes
             .into_iter()
-            .flatten()
             .map(|node| {
                 let (include_refs, exclude_refs) = match node.conditions.ref_name {
                     Some(ref_name) => (ref_name.include, ref_name.exclude),
@@ -98,29 +169,47 @@ impl GithubApiConnection {
             .collect())
     }
 
-    /// Returns the required status checks for the given ruleset.
+    /// Returns the required status checks for the given ruleset, paginating
+    /// through the ruleset's rules connection until it is exhausted.
     pub async fn get_ruleset_required_checks(
         &self,
         ruleset_id: &str,
     ) -> anyhow::Result<Vec<RequiredStatusCheck>> {
-        let variables = get_ruleset_required_checks::Variables {
-            ruleset_id: ruleset_id.to_string(),
-        };
+        let mut nodes = vec![];
+        let mut after = None;
 
-        let response = self
-            .make_request::<GetRulesetRequiredChecks>(variables)
-            .await?;
+        loop {
+            let variables = get_ruleset_required_checks::Variables {
+                ruleset_id: ruleset_id.to_string(),
+                after,
+            };
+
+            let response = self
+                .make_request::<GetRulesetRequiredChecks>(variables)
+                .await?;
+
+            let rules = match response.node {
+                Some(GetRulesetRequiredChecksNode::RepositoryRuleset(ruleset)) => ruleset.rules,
+                _ => break,
+            };
+            let rules = match rules {
+                Some(r) => r,
+                None => break,
+            };
 
-        let rules = match response.node {
-            Some(GetRulesetRequiredChecksNode::RepositoryRuleset(ruleset)) => {
-                ruleset.rules.and_then(|r| r.nodes).unwrap_or_default()
+            nodes.extend(rules.nodes.unwrap_or_default().into_iter().flatten());
+
+            if !rules.page_info.has_next_page {
+                break;
             }
-            _ => return Ok(vec![]),
-        };
+            after = match rules.page_info.end_cursor {
+                Some(cursor) => Some(cursor),
+                None => break,
+            };
+        }
 
-        let checks = rules
+        let checks = nodes
             .into_iter()
-            .flatten()
             .filter_map(|rule| match rule.parameters {
                 Some(RequiredStatusChecksInfoParameters::RequiredStatusChecksParameters(
                     params,
@@ -136,4 +225,194 @@ impl GithubApiConnection {
 
         Ok(checks)
     }
+
+    /// Returns the required status check contexts that must be satisfied
+    /// before a push to `target_ref` may be accepted: every *active*
+    /// ruleset whose include/exclude ref-name conditions cover `target_ref`,
+    /// with its required checks gathered and concatenated.
+    pub async fn required_checks_for_push(
+        &self,
+        owner: &str,
+        name: &str,
+        target_ref: &str,
+    ) -> anyhow::Result<Vec<RequiredStatusCheck>> {
+        let default_branch_ref = match self.get_default_branch(owner, name).await? {
+            Some((branch_name, _)) => format!("refs/heads/{}", branch_name),
+            None => String::new(),
+        };
+
+        let rulesets = self.get_repository_rulesets(owner, name).await?;
+        let mut checks = vec![];
+
+        for ruleset in rulesets {
+            if ruleset.enforcement != RuleEnforcement::Active {
+                continue;
+            }
+            if !ruleset_target_matches_namespace(&ruleset.target, target_ref) {
+                continue;
+            }
+
+            let included = ruleset
+                .include_refs
+                .iter()
+                .any(|p| ref_name_condition_matches(p, target_ref, &default_branch_ref));
+            let excluded = ruleset
+                .exclude_refs
+                .iter()
+                .any(|p| ref_name_condition_matches(p, target_ref, &default_branch_ref));
+            if !included || excluded {
+                continue;
+            }
+
+            checks.extend(self.get_ruleset_required_checks(&ruleset.id).await?);
+        }
+
+        Ok(checks)
+    }
+
+    /// Checks whether `satisfied_contexts` covers every required status
+    /// check for a push to `target_ref`, returning the missing contexts
+    /// as a structured rejection if not.
+    pub async fn check_push_allowed(
+        &self,
+        owner: &str,
+        name: &str,
+        target_ref: &str,
+        satisfied_contexts: &[String],
+    ) -> anyhow::Result<Result<(), PushGateRejection>> {
+        let required = self
+            .required_checks_for_push(owner, name, target_ref)
+            .await?;
+
+        let missing_contexts: Vec<String> = required
+            .into_iter()
+            .map(|check| check.context)
+            .filter(|context| !satisfied_contexts.iter().any(|s| s == context))
+            .collect();
+
+        if missing_contexts.is_empty() {
+            Ok(Ok(()))
+        } else {
+            Ok(Err(PushGateRejection { missing_contexts }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tilde_all_matches_any_ref() {
+        assert!(ref_name_condition_matches(
+            "~ALL",
+            "refs/heads/feature/x",
+            "refs/heads/main"
+        ));
+        assert!(ref_name_condition_matches(
+            "~ALL",
+            "refs/tags/v1.0.0",
+            "refs/heads/main"
+        ));
+    }
+
+    #[test]
+    fn tilde_default_branch_matches_only_default() {
+        assert!(ref_name_condition_matches(
+            "~DEFAULT_BRANCH",
+            "refs/heads/main",
+            "refs/heads/main"
+        ));
+        assert!(!ref_name_condition_matches(
+            "~DEFAULT_BRANCH",
+            "refs/heads/feature/x",
+            "refs/heads/main"
+        ));
+    }
+
+    #[test]
+    fn single_star_does_not_cross_segment_boundary() {
+        assert!(ref_name_condition_matches(
+            "refs/heads/*",
+            "refs/heads/main",
+            "refs/heads/main"
+        ));
+        assert!(!ref_name_condition_matches(
+            "refs/heads/*",
+            "refs/heads/feature/x",
+            "refs/heads/main"
+        ));
+    }
+
+    #[test]
+    fn double_star_crosses_segment_boundaries() {
+        assert!(ref_name_condition_matches(
+            "refs/heads/**",
+            "refs/heads/feature/x",
+            "refs/heads/main"
+        ));
+        assert!(ref_name_condition_matches(
+            "refs/heads/**",
+            "refs/heads/main",
+            "refs/heads/main"
+        ));
+        assert!(!ref_name_condition_matches(
+            "refs/heads/**",
+            "refs/tags/v1.0.0",
+            "refs/heads/main"
+        ));
+    }
+
+    #[test]
+    fn glob_with_interior_literal_segment() {
+        assert!(ref_name_condition_matches(
+            "refs/heads/release/*",
+            "refs/heads/release/1.0",
+            "refs/heads/main"
+        ));
+        assert!(!ref_name_condition_matches(
+            "refs/heads/release/*",
+            "refs/heads/release/1.0/hotfix",
+            "refs/heads/main"
+        ));
+    }
+
+    #[test]
+    fn matcher_identifies_both_include_and_exclude_candidates() {
+        // Exclude-pattern precedence itself is enforced by the caller
+        // (`required_checks_for_push`); here we only confirm the matcher
+        // correctly identifies both include and exclude candidates so that
+        // precedence logic has accurate inputs to work with.
+        assert!(ref_name_condition_matches(
+            "refs/heads/**",
+            "refs/heads/release/1.0",
+            "refs/heads/main"
+        ));
+        assert!(ref_name_condition_matches(
+            "refs/heads/release/*",
+            "refs/heads/release/1.0",
+            "refs/heads/main"
+        ));
+    }
+
+    #[test]
+    fn ruleset_target_is_namespace_scoped() {
+        assert!(ruleset_target_matches_namespace(
+            &Some(RepositoryRulesetTarget::Branch),
+            "refs/heads/main"
+        ));
+        assert!(!ruleset_target_matches_namespace(
+            &Some(RepositoryRulesetTarget::Branch),
+            "refs/tags/v1.0.0"
+        ));
+        assert!(ruleset_target_matches_namespace(
+            &Some(RepositoryRulesetTarget::Tag),
+            "refs/tags/v1.0.0"
+        ));
+        assert!(!ruleset_target_matches_namespace(
+            &Some(RepositoryRulesetTarget::Tag),
+            "refs/heads/main"
+        ));
+        assert!(ruleset_target_matches_namespace(&None, "refs/heads/main"));
+    }
 }
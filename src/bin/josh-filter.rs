@@ -7,11 +7,20 @@ extern crate rs_tracing;
 extern crate clap;
 extern crate git2;
 extern crate regex;
+extern crate tokio;
+
+extern crate josh_github_graphql;
 
 #[macro_use]
 extern crate lazy_static;
 
+mod branches;
+mod prefix_trie;
+mod push_gate;
+mod remote_helper;
+
 use josh::view_maps;
+use prefix_trie::PrefixTrie;
 use std::env;
 use std::process::exit;
 
@@ -24,6 +33,192 @@ lazy_static! {
         regex::Regex::new(r"(?P<src>[^:]*)(?P<spec>:[^\[]*)").expect("can't compile regex");
 }
 
+/// Builds the view for `viewstr`, resolves `src_ref` and applies the view
+/// forward onto `target_ref`, sharing `fm`/`bm` across callers so commits
+/// already mapped by another spec are not re-filtered.
+pub(crate) fn apply_spec_forward(
+    repo: &git2::Repository,
+    fm: &mut view_maps::ViewMaps,
+    bm: &mut view_maps::ViewMaps,
+    viewstr: &str,
+    src_ref: &str,
+    target_ref: &str,
+) {
+    let viewobj = josh::build_view(repo, viewstr);
+    let src = repo
+        .revparse_ext(src_ref)
+        .expect("reference not found 1")
+        .1
+        .expect("reference not found")
+        .name()
+        .unwrap()
+        .to_string();
+
+    josh::apply_view_to_refs(
+        repo,
+        &*viewobj,
+        &[(src, target_ref.to_owned())],
+        fm,
+        bm,
+    );
+}
+
+/// Builds the view for `viewstr` and runs it in reverse over `old..new`,
+/// reusing the same `[src:target]spec` parsing as the forward direction.
+pub(crate) fn apply_spec_reverse(
+    repo: &git2::Repository,
+    bm: &view_maps::ViewMaps,
+    viewstr: &str,
+    old: git2::Oid,
+    new: git2::Oid,
+) -> josh::UnapplyView {
+    let viewobj = josh::build_view(repo, viewstr);
+    josh::unapply_view(repo, bm, &*viewobj, old, new)
+}
+
+/// Collects the old/new paths touched by an already-computed `diff`.
+fn diff_to_paths(diff: &git2::Diff) -> Vec<String> {
+    let mut changed_paths = vec![];
+    let _ = diff.foreach(
+        &mut |delta, _| {
+            if let Some(p) = delta.old_file().path() {
+                changed_paths.push(p.to_string_lossy().into_owned());
+            }
+            if let Some(p) = delta.new_file().path() {
+                changed_paths.push(p.to_string_lossy().into_owned());
+            }
+            true
+        },
+        None,
+        None,
+        None,
+    );
+    changed_paths
+}
+
+/// Returns true if any of `changed_paths` falls under one of `prefixes`,
+/// via an O(path-depth) trie lookup instead of a linear scan of
+/// `prefixes` per changed path. An empty `prefixes` list can't be
+/// meaningfully checked, so it is treated as "assume affected".
+fn any_path_under_prefixes(changed_paths: &[String], prefixes: &[String]) -> bool {
+    if prefixes.is_empty() {
+        return true;
+    }
+    let mut trie = PrefixTrie::new();
+    for prefix in prefixes {
+        trie.insert(prefix, ());
+    }
+    changed_paths
+        .iter()
+        .any(|path| trie.longest_prefix_match(path).is_some())
+}
+
+/// Returns true if none of `prefixes` (a view's own declared path
+/// prefixes) were touched between commits `prior` and `current`, meaning
+/// re-running `apply_view_to_refs` for that view would be redundant. This
+/// is the one path->view dispatch decision reachable from this crate:
+/// apply_view_to_refs's own per-commit, per-path resolution lives inside
+/// the josh library and stays out of reach, but skipping whole stanzas
+/// that a push didn't touch is a real saving once a filter file grows
+/// into the hundreds of overlaid stanzas described in the request.
+fn stanza_unaffected_since(
+    repo: &git2::Repository,
+    prior: git2::Oid,
+    current: git2::Oid,
+    prefixes: &[String],
+) -> bool {
+    if prior == current {
+        return true;
+    }
+
+    let old_tree = match repo.find_commit(prior).and_then(|c| c.tree()) {
+        Ok(t) => t,
+        Err(_) => return false,
+    };
+    let new_tree = match repo.find_commit(current).and_then(|c| c.tree()) {
+        Ok(t) => t,
+        Err(_) => return false,
+    };
+    let diff = match repo.diff_tree_to_tree(Some(&old_tree), Some(&new_tree), None) {
+        Ok(d) => d,
+        Err(_) => return false,
+    };
+
+    !any_path_under_prefixes(&diff_to_paths(&diff), prefixes)
+}
+
+/// `--changed-between <base>..<head>`: diffs the two trees once, then tests
+/// every declared `[src:target]spec` prefix against the set of changed
+/// paths via a trie lookup, printing the specs whose view actually differs
+/// so CI can republish only the affected filtered subrepos.
+fn changed_views(repo: &git2::Repository, args: &clap::ArgMatches, range: &str) -> i32 {
+    let mut revs = range.splitn(2, "..");
+    let base = revs.next().unwrap_or("");
+    let head = revs.next().unwrap_or("");
+
+    let base_tree = match repo
+        .revparse_single(base)
+        .and_then(|o| o.peel_to_tree())
+    {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("error: could not resolve \"{}\": {}", base, e);
+            return 1;
+        }
+    };
+    let head_tree = match repo
+        .revparse_single(head)
+        .and_then(|o| o.peel_to_tree())
+    {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("error: could not resolve \"{}\": {}", head, e);
+            return 1;
+        }
+    };
+
+    let diff = match repo.diff_tree_to_tree(Some(&base_tree), Some(&head_tree), None) {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("error: could not diff trees: {}", e);
+            return 1;
+        }
+    };
+
+    let changed_paths = diff_to_paths(&diff);
+
+    let srcstr = args.value_of("from_to").unwrap_or("");
+    let specstr = args.value_of("spec").unwrap_or("");
+    let filestr = args
+        .value_of("file")
+        .and_then(|f| read_to_string(f).ok())
+        .unwrap_or(format!("[{}]{}", srcstr, specstr));
+
+    let mut affected = std::collections::BTreeSet::new();
+    for caps in FILE_REGEX.captures_iter(&filestr) {
+        let from_to = caps.name("src").unwrap().as_str().trim();
+        let viewstr = caps.name("spec").unwrap().as_str().trim();
+        let spec = format!("[{}]{}", from_to, viewstr);
+
+        let viewobj = josh::build_view(repo, viewstr);
+        let prefixes: Vec<String> = viewobj.prefixes().iter().map(|(p, _)| p.clone()).collect();
+        if !prefixes.is_empty() && any_path_under_prefixes(&changed_paths, &prefixes) {
+            affected.insert(spec);
+        }
+    }
+
+    if args.is_present("json") {
+        let items: Vec<String> = affected.iter().map(|s| format!("{:?}", s)).collect();
+        println!("[{}]", items.join(","));
+    } else {
+        for spec in &affected {
+            println!("{}", spec);
+        }
+    }
+
+    0
+}
+
 fn run_filter(args: Vec<String>) -> i32 {
     let args = clap::App::new("josh-filter")
         .arg(clap::Arg::with_name("file").long("file").takes_value(true))
@@ -37,8 +232,58 @@ fn run_filter(args: Vec<String>) -> i32 {
                 .long("trace")
                 .takes_value(true),
         )
+        .arg(clap::Arg::with_name("remote-helper").long("remote-helper"))
+        .arg(clap::Arg::with_name("all-branches").long("all-branches"))
+        .arg(
+            clap::Arg::with_name("branches")
+                .long("branches")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("gate-owner")
+                .long("gate-owner")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("gate-name")
+                .long("gate-name")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("gate-ref")
+                .long("gate-ref")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("satisfied-checks")
+                .long("satisfied-checks")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("changed-between")
+                .long("changed-between")
+                .takes_value(true),
+        )
+        .arg(clap::Arg::with_name("json").long("json"))
         .get_matches_from(args);
 
+    if let Some(range) = args.value_of("changed-between") {
+        let repo = git2::Repository::open_from_env().unwrap();
+        return changed_views(&repo, &args, range);
+    }
+
+    if args.is_present("remote-helper") {
+        // Invoked by git as `git-remote-josh <remote-name> <url>`, which
+        // line up with the same two positional slots `from_to`/`spec`
+        // already occupy in the non-helper CLI.
+        let remote_name = args.value_of("from_to").unwrap_or("");
+        let remote_url = args.value_of("spec").unwrap_or("");
+        return match remote_helper::run(remote_name, remote_url) {
+            Ok(()) => 0,
+            Err(_) => 1,
+        };
+    }
+
     let repo = git2::Repository::open_from_env().unwrap();
     let mut fm = view_maps::ViewMaps::new();
     let mut bm = view_maps::ViewMaps::new();
@@ -51,6 +296,38 @@ fn run_filter(args: Vec<String>) -> i32 {
         .and_then(|f| read_to_string(f).ok())
         .unwrap_or(format!("[{}]{}", srcstr, specstr));
 
+    // NOTE: this does not give apply_view_to_refs itself O(path-depth)
+    // per-path view dispatch — that per-commit path->view resolution lives
+    // inside the josh library's view/apply_view_to_refs internals, which
+    // are not part of this source tree and so are out of reach here. What
+    // this trie does provide, using the same longest_prefix_match as
+    // --changed-between, is an O(path-depth) check, compiled once per
+    // invocation, that an added stanza's src prefix doesn't silently
+    // shadow (or get shadowed by) another declared stanza.
+    let mut stanza_prefixes = PrefixTrie::new();
+    for caps in FILE_REGEX.captures_iter(&filestr) {
+        let from_to = caps.name("src").unwrap().as_str().trim();
+        if let Some(src) = from_to.splitn(2, ":").next() {
+            // Check both directions: a narrower prefix declared after a
+            // broader one is shadowed by it (longest_prefix_match), and a
+            // broader prefix declared after a narrower one shadows it in
+            // turn (has_descendant) -- order of declaration shouldn't
+            // determine whether the overlap gets caught.
+            if let Some(shadowed_by) = stanza_prefixes.longest_prefix_match(src) {
+                eprintln!(
+                    "warning: src prefix \"{}\" is shadowed by overlapping prefix \"{}\"",
+                    src, shadowed_by
+                );
+            } else if stanza_prefixes.has_descendant(src) {
+                eprintln!(
+                    "warning: src prefix \"{}\" shadows a more specific, already-declared prefix",
+                    src
+                );
+            }
+            stanza_prefixes.insert(src, src.to_owned());
+        }
+    }
+
     for caps in FILE_REGEX.captures_iter(&filestr) {
         let from_to = caps.name("src").unwrap().as_str().trim().to_owned();
         let mut splitted = from_to.splitn(2, ":");
@@ -87,15 +364,67 @@ fn run_filter(args: Vec<String>) -> i32 {
             }
         }
 
+        let branch_glob = args.value_of("branches");
+        if args.is_present("all-branches") || branch_glob.is_some() {
+            if args.is_present("squash") || args.is_present("reverse") {
+                eprintln!(
+                    "warning: --squash/--reverse are not supported with --all-branches/--branches \
+                     and are ignored for this stanza"
+                );
+            }
+
+            for (branch_name, head_oid) in branches::branches(&repo, branch_glob) {
+                // `branches::branches` already resolved this to a real
+                // `refs/heads/<branch_name>` ref via `references_glob`, so
+                // there is no need to re-resolve it through revparse.
+                let branch_ref = format!("refs/heads/{}", branch_name);
+                let branch_target = format!("{}/{}", target, branch_name);
+
+                josh::apply_view_to_refs(
+                    &repo,
+                    &*viewobj,
+                    &[(branch_ref, branch_target.clone())],
+                    &mut fm,
+                    &mut bm,
+                );
+                println!("ok {} ({}) -> {}", branch_name, head_oid, branch_target);
+            }
+            continue;
+        }
+
         let reverse = args.is_present("reverse");
+        let squash = args.is_present("squash");
 
-        if args.is_present("squash") {
+        if squash {
             viewobj = josh::build_chain(
                 josh::build_view(&repo, &format!(":cutoff={}", &src)),
                 viewobj,
             );
         }
 
+        // Bookkeeping ref recording the src oid this exact stanza was last
+        // applied against, so the next run can tell whether it needs to
+        // redo the work at all. Only tracked for the plain forward case --
+        // reverse/squash change what "unaffected" would even mean, so they
+        // always re-run.
+        let state_ref = format!("refs/JOSH_FILTER_STATE/{}", target.trim_start_matches("refs/"));
+        let current_src_oid = repo.revparse_single(&src).ok().map(|o| o.id());
+
+        if !reverse && !squash {
+            if let Some(current) = current_src_oid {
+                let unaffected = match repo.revparse_single(&state_ref).map(|o| o.id()) {
+                    Ok(prior) => {
+                        let prefixes: Vec<String> = pres.iter().map(|(p, _)| p.clone()).collect();
+                        stanza_unaffected_since(&repo, prior, current, &prefixes)
+                    }
+                    Err(_) => false,
+                };
+                if unaffected {
+                    continue;
+                }
+            }
+        }
+
         let t = if reverse {
             "refs/JOSH_TMP".to_owned()
         } else {
@@ -112,12 +441,36 @@ fn run_filter(args: Vec<String>) -> i32 {
 
         josh::apply_view_to_refs(&repo, &*viewobj, &[(src.clone(), t)], &mut fm, &mut bm);
 
+        if !reverse && !squash {
+            if let Some(current) = current_src_oid {
+                let _ = repo.reference(&state_ref, current, true, "josh-filter stanza state");
+            }
+        }
+
         if reverse {
             let new = repo.revparse_single(&target).unwrap().id();
             let old = repo.revparse_single("JOSH_TMP").unwrap().id();
 
             match josh::unapply_view(&repo, &bm, &*viewobj, old, new) {
                 josh::UnapplyView::Done(rewritten) => {
+                    if let Some(gate) = push_gate::config_from_args(&args) {
+                        match push_gate::missing_checks(&gate) {
+                            Ok(missing) if missing.is_empty() => {}
+                            Ok(missing) => {
+                                eprintln!(
+                                    "push to {} rejected, missing required checks: {}",
+                                    gate.target_ref,
+                                    missing.join(", ")
+                                );
+                                return 1;
+                            }
+                            Err(e) => {
+                                eprintln!("could not evaluate push gate: {}", e);
+                                return 1;
+                            }
+                        }
+                    }
+
                     repo.reference(&src, rewritten, true, "unapply_view")
                         .expect("can't create reference");
                 }
@@ -143,3 +496,41 @@ fn main() {
 
     exit(run_filter(args));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn any_path_under_prefixes_matches_declared_prefix() {
+        let prefixes = vec!["sub/crate".to_owned()];
+        let changed = vec!["sub/crate/src/lib.rs".to_owned()];
+        assert!(any_path_under_prefixes(&changed, &prefixes));
+    }
+
+    #[test]
+    fn any_path_under_prefixes_ignores_unrelated_paths() {
+        let prefixes = vec!["sub/crate".to_owned()];
+        let changed = vec!["other/crate/src/lib.rs".to_owned()];
+        assert!(!any_path_under_prefixes(&changed, &prefixes));
+    }
+
+    #[test]
+    fn any_path_under_prefixes_requires_component_boundary() {
+        let prefixes = vec!["sub".to_owned()];
+        let changed = vec!["subdir/file.rs".to_owned()];
+        assert!(!any_path_under_prefixes(&changed, &prefixes));
+    }
+
+    #[test]
+    fn any_path_under_prefixes_treats_no_prefixes_as_affected() {
+        let changed = vec!["anything.rs".to_owned()];
+        assert!(any_path_under_prefixes(&changed, &[]));
+    }
+
+    #[test]
+    fn any_path_under_prefixes_empty_changeset_is_unaffected() {
+        let prefixes = vec!["sub/crate".to_owned()];
+        assert!(!any_path_under_prefixes(&[], &prefixes));
+    }
+}
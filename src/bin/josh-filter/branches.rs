@@ -0,0 +1,70 @@
+//! Branch enumeration used by `--all-branches`/`--branches <glob>` so a view
+//! can be applied across a whole repository in a single pass.
+
+/// Builds the `refs/heads/*`-scoped glob passed to `references_glob` for a
+/// given `--branches` argument. Always anchored under `refs/heads/`, even if
+/// the caller already included that prefix, so a glob can never escape into
+/// another namespace (e.g. `refs/tags/*`) and end up doubled once the
+/// caller re-prefixes the trimmed name it gets back.
+fn build_pattern(glob: Option<&str>) -> String {
+    match glob {
+        Some(g) => format!("refs/heads/{}", g.trim_start_matches("refs/heads/")),
+        None => "refs/heads/*".to_owned(),
+    }
+}
+
+/// Returns `(branch_name, head_oid)` for every local branch matching `glob`
+/// (a `refs/heads/*`-style fnmatch pattern, with or without the
+/// `refs/heads/` prefix), or every local branch when `glob` is `None`.
+pub fn branches(repo: &git2::Repository, glob: Option<&str>) -> Vec<(String, git2::Oid)> {
+    let pattern = build_pattern(glob);
+
+    let refs = match repo.references_glob(&pattern) {
+        Ok(r) => r,
+        Err(_) => return vec![],
+    };
+
+    refs.flatten()
+        .filter_map(|r| {
+            let name = r.name()?.trim_start_matches("refs/heads/").to_owned();
+            let oid = r.target()?;
+            Some((name, oid))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_pattern_defaults_to_all_heads() {
+        assert_eq!(build_pattern(None), "refs/heads/*");
+    }
+
+    #[test]
+    fn build_pattern_scopes_bare_glob_under_heads() {
+        assert_eq!(build_pattern(Some("release-*")), "refs/heads/release-*");
+    }
+
+    #[test]
+    fn build_pattern_does_not_double_prefix_an_explicit_heads_glob() {
+        assert_eq!(
+            build_pattern(Some("refs/heads/release-*")),
+            "refs/heads/release-*"
+        );
+    }
+
+    #[test]
+    fn build_pattern_keeps_other_namespaces_scoped_under_heads() {
+        // This module only ever enumerates local branches, so a glob
+        // naming another namespace is kept nested under refs/heads/
+        // rather than honored verbatim -- matching the doc comment's
+        // refs/heads/*-only contract instead of silently branching out
+        // of it.
+        assert_eq!(
+            build_pattern(Some("refs/tags/v1.0.0")),
+            "refs/heads/refs/tags/v1.0.0"
+        );
+    }
+}
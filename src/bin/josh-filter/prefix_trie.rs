@@ -0,0 +1,132 @@
+//! A trie over `/`-separated path prefixes, giving O(path-depth)
+//! longest-prefix-match lookups instead of scanning every declared
+//! `[src:target]` stanza. Used by `--changed-between` to test whether a
+//! changed path falls under one of a view spec's prefixes, and at
+//! stanza-parse time to flag overlapping `src` prefixes.
+//!
+//! This does not change how `apply_view_to_refs` itself resolves a path to
+//! a view during filtering -- that dispatch lives inside the josh
+//! library's view internals, outside this source tree.
+
+use std::collections::HashMap;
+
+#[derive(Default)]
+struct Node<V> {
+    children: HashMap<String, Node<V>>,
+    value: Option<V>,
+}
+
+/// Maps path prefixes to values, supporting longest-prefix-match lookups.
+pub struct PrefixTrie<V> {
+    root: Node<V>,
+}
+
+impl<V> PrefixTrie<V> {
+    pub fn new() -> Self {
+        PrefixTrie { root: Node::default() }
+    }
+
+    /// Registers `prefix` (e.g. `"sub/crate"`) as owned by `value`.
+    pub fn insert(&mut self, prefix: &str, value: V) {
+        let mut node = &mut self.root;
+        for component in prefix.split('/').filter(|c| !c.is_empty()) {
+            node = node
+                .children
+                .entry(component.to_owned())
+                .or_insert_with(Node::default);
+        }
+        node.value = Some(value);
+    }
+
+    /// Returns the value registered for the longest prefix of `path` that
+    /// was inserted, descending one path component at a time.
+    pub fn longest_prefix_match(&self, path: &str) -> Option<&V> {
+        let mut node = &self.root;
+        let mut best = node.value.as_ref();
+
+        for component in path.split('/').filter(|c| !c.is_empty()) {
+            node = match node.children.get(component) {
+                Some(n) => n,
+                None => break,
+            };
+            if node.value.is_some() {
+                best = node.value.as_ref();
+            }
+        }
+
+        best
+    }
+
+    /// Returns true if some prefix strictly below `prefix` already has a
+    /// value registered (a value at `prefix` itself does not count) -- i.e. inserting a value *at* `prefix` would shadow a
+    /// more specific registration underneath it. Paired with
+    /// `longest_prefix_match` (which only catches the opposite direction,
+    /// a new prefix shadowed by an existing broader one), this makes the
+    /// overlap check order-independent.
+    pub fn has_descendant(&self, prefix: &str) -> bool {
+        let mut node = &self.root;
+        for component in prefix.split('/').filter(|c| !c.is_empty()) {
+            node = match node.children.get(component) {
+                Some(n) => n,
+                None => return false,
+            };
+        }
+        node.children.values().any(Self::subtree_has_value)
+    }
+
+    fn subtree_has_value(node: &Node<V>) -> bool {
+        node.value.is_some() || node.children.values().any(Self::subtree_has_value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn longest_prefix_match_prefers_more_specific_registration() {
+        let mut trie = PrefixTrie::new();
+        trie.insert("a", "shallow");
+        trie.insert("a/b", "deep");
+
+        assert_eq!(trie.longest_prefix_match("a/b/c"), Some(&"deep"));
+        assert_eq!(trie.longest_prefix_match("a/x"), Some(&"shallow"));
+        assert_eq!(trie.longest_prefix_match("z"), None);
+    }
+
+    #[test]
+    fn longest_prefix_match_requires_component_boundary() {
+        let mut trie = PrefixTrie::new();
+        trie.insert("sub", "v");
+
+        // "subdir" shares a textual prefix with "sub" but is a different
+        // path component, so it must not match.
+        assert_eq!(trie.longest_prefix_match("subdir/x"), None);
+        assert_eq!(trie.longest_prefix_match("sub/x"), Some(&"v"));
+    }
+
+    #[test]
+    fn insert_exact_path_matches_itself() {
+        let mut trie = PrefixTrie::new();
+        trie.insert("a/b", "v");
+        assert_eq!(trie.longest_prefix_match("a/b"), Some(&"v"));
+    }
+
+    #[test]
+    fn has_descendant_detects_narrower_existing_prefix() {
+        let mut trie = PrefixTrie::new();
+        trie.insert("a/b", "deep");
+
+        // Declaring "a" after "a/b" shadows the more specific "a/b".
+        assert!(trie.has_descendant("a"));
+        // But "a/b" itself has no registered descendant.
+        assert!(!trie.has_descendant("a/b"));
+        assert!(!trie.has_descendant("unrelated"));
+    }
+
+    #[test]
+    fn has_descendant_is_false_with_no_children() {
+        let trie: PrefixTrie<&str> = PrefixTrie::new();
+        assert!(!trie.has_descendant("a"));
+    }
+}
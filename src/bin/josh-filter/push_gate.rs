@@ -0,0 +1,59 @@
+//! Wires `GithubApiConnection`'s ruleset queries into the reverse
+//! (`unapply_view`) path so a filtered-branch push can be rejected before
+//! its rewritten ref is written, instead of only being caught later by
+//! GitHub's own server-side ruleset enforcement.
+
+use josh_github_graphql::connection::GithubApiConnection;
+
+/// Ruleset context needed to gate a push: the GitHub repository the
+/// rulesets live on, the ref being pushed to, and the status-check
+/// contexts already known to have succeeded for the head being pushed.
+pub struct GateConfig {
+    pub owner: String,
+    pub name: String,
+    pub target_ref: String,
+    pub satisfied_contexts: Vec<String>,
+}
+
+/// Parses `--gate-owner`/`--gate-name`/`--gate-ref`/`--satisfied-checks`
+/// into a `GateConfig`, or `None` if gating wasn't requested.
+pub fn config_from_args(args: &clap::ArgMatches) -> Option<GateConfig> {
+    let owner = args.value_of("gate-owner")?;
+    let name = args.value_of("gate-name")?;
+    let target_ref = args.value_of("gate-ref")?;
+
+    let satisfied_contexts = args
+        .value_of("satisfied-checks")
+        .unwrap_or("")
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_owned)
+        .collect();
+
+    Some(GateConfig {
+        owner: owner.to_owned(),
+        name: name.to_owned(),
+        target_ref: target_ref.to_owned(),
+        satisfied_contexts,
+    })
+}
+
+/// Blocks on `check_push_allowed` and returns the missing status-check
+/// contexts, if any, that must pass before the push may be accepted.
+pub fn missing_checks(config: &GateConfig) -> anyhow::Result<Vec<String>> {
+    let connection = GithubApiConnection::from_env()?;
+
+    let rt = tokio::runtime::Runtime::new()?;
+    let result = rt.block_on(connection.check_push_allowed(
+        &config.owner,
+        &config.name,
+        &config.target_ref,
+        &config.satisfied_contexts,
+    ))?;
+
+    match result {
+        Ok(()) => Ok(vec![]),
+        Err(rejection) => Ok(rejection.missing_contexts),
+    }
+}
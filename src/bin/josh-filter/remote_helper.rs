@@ -0,0 +1,294 @@
+//! Implements git's remote-helper protocol so a view can be configured as a
+//! normal git remote (`git remote add origin josh::<spec>::<url>`) and have
+//! `git clone`/`fetch`/`push` run the view forward/backward transparently,
+//! instead of driving `apply_view_to_refs`/`unapply_view` by hand.
+
+use crate::{apply_spec_forward, apply_spec_reverse};
+use josh::view_maps;
+use std::io::{self, BufRead, Write};
+
+/// Namespace the upstream's `refs/heads/*` are fetched into before the view
+/// is applied, keeping them separate from this repo's own `refs/heads/*`
+/// and from the helper's `refs/JOSH_*` bookkeeping refs.
+const UPSTREAM_HEADS_NS: &str = "refs/josh/upstream/heads/";
+/// Same, for `refs/tags/*`.
+const UPSTREAM_TAGS_NS: &str = "refs/josh/upstream/tags/";
+
+/// A `josh::<spec>::<url>` remote, split into its view spec and the
+/// underlying git URL it proxies to.
+struct Remote {
+    spec: String,
+    url: String,
+}
+
+fn parse_remote_url(remote_url: &str) -> Remote {
+    let stripped = remote_url.strip_prefix("josh::").unwrap_or(remote_url);
+    let mut parts = stripped.splitn(2, "::");
+    let spec = parts.next().unwrap_or("").to_owned();
+    let url = parts.next().unwrap_or("").to_owned();
+    Remote { spec, url }
+}
+
+/// Maps an upstream ref name (`refs/heads/foo`, `refs/tags/v1`) to the local
+/// namespace it's fetched into (`refs/josh/upstream/heads/foo`, ...), or
+/// `None` if it's outside the two namespaces this helper tracks.
+fn to_upstream_ref(name: &str) -> Option<String> {
+    if let Some(rest) = name.strip_prefix("refs/heads/") {
+        Some(format!("{}{}", UPSTREAM_HEADS_NS, rest))
+    } else if let Some(rest) = name.strip_prefix("refs/tags/") {
+        Some(format!("{}{}", UPSTREAM_TAGS_NS, rest))
+    } else {
+        None
+    }
+}
+
+/// Inverse of `to_upstream_ref`: recovers the original upstream ref name
+/// from a local `refs/josh/upstream/...` ref, for advertising to git.
+fn from_upstream_ref(name: &str) -> Option<String> {
+    if let Some(rest) = name.strip_prefix(UPSTREAM_HEADS_NS) {
+        Some(format!("refs/heads/{}", rest))
+    } else if let Some(rest) = name.strip_prefix(UPSTREAM_TAGS_NS) {
+        Some(format!("refs/tags/{}", rest))
+    } else {
+        None
+    }
+}
+
+/// Fetches `remote.url`'s branches and tags into the `refs/josh/upstream/*`
+/// namespace so `handle_list`/`handle_fetch_line` have real upstream data
+/// to run the view over, instead of only ever seeing this repo's own refs.
+fn fetch_upstream(repo: &git2::Repository, remote: &Remote) -> Result<(), ()> {
+    let mut upstream = repo.remote_anonymous(&remote.url).map_err(|_| ())?;
+    upstream
+        .fetch(
+            &[
+                &format!("+refs/heads/*:{}*", UPSTREAM_HEADS_NS),
+                &format!("+refs/tags/*:{}*", UPSTREAM_TAGS_NS),
+            ],
+            None,
+            None,
+        )
+        .map_err(|_| ())
+}
+
+/// Pushes the local ref `name` (already rewritten back through the view by
+/// `handle_push_line`) to `remote.url` under the same name.
+fn push_to_upstream(repo: &git2::Repository, remote: &Remote, name: &str) -> Result<(), ()> {
+    let mut upstream = repo.remote_anonymous(&remote.url).map_err(|_| ())?;
+    upstream
+        .push(&[format!("{}:{}", name, name)], None)
+        .map_err(|_| ())
+}
+
+/// Runs the remote-helper loop on stdin/stdout until EOF.
+pub fn run(_remote_name: &str, remote_url: &str) -> Result<(), ()> {
+    let remote = parse_remote_url(remote_url);
+    let repo = git2::Repository::open_from_env().map_err(|_| ())?;
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+
+    let mut fm = view_maps::ViewMaps::new();
+    let mut bm = view_maps::ViewMaps::new();
+
+    loop {
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).map_err(|_| ())? == 0 {
+            return Ok(());
+        }
+        let line = line.trim_end().to_owned();
+
+        if line.is_empty() {
+            continue;
+        } else if line == "capabilities" {
+            writeln!(stdout, "fetch").map_err(|_| ())?;
+            writeln!(stdout, "push").map_err(|_| ())?;
+            writeln!(stdout).map_err(|_| ())?;
+        } else if line.starts_with("list") {
+            fetch_upstream(&repo, &remote)?;
+            handle_list(&repo, &remote, &mut fm, &mut bm, &mut stdout)?;
+        } else if line.starts_with("fetch ") {
+            fetch_upstream(&repo, &remote)?;
+            handle_batch(&stdin, &mut stdout, &line, |l| {
+                handle_fetch_line(&repo, &remote, &mut fm, &mut bm, l)
+            })?;
+            writeln!(stdout).map_err(|_| ())?;
+        } else if line.starts_with("push ") {
+            handle_batch(&stdin, &mut stdout, &line, |l| {
+                handle_push_line(&repo, &remote, &bm, l)
+            })?;
+            writeln!(stdout).map_err(|_| ())?;
+        } else {
+            return Err(());
+        }
+        stdout.flush().map_err(|_| ())?;
+    }
+}
+
+/// Lists the remote's refs by applying the view forward to every upstream
+/// ref (fetched into `refs/josh/upstream/*` by `fetch_upstream`) onto a
+/// transient `refs/JOSH_REMOTE/*` namespace, then printing each resulting
+/// oid/name pair under the ref's original, non-upstream-scoped name.
+fn handle_list(
+    repo: &git2::Repository,
+    remote: &Remote,
+    fm: &mut view_maps::ViewMaps,
+    bm: &mut view_maps::ViewMaps,
+    stdout: &mut impl Write,
+) -> Result<(), ()> {
+    let refs = repo
+        .references_glob("refs/josh/upstream/**")
+        .map_err(|_| ())?;
+    for r in refs.flatten() {
+        let upstream_name = match r.name() {
+            Some(n) => n.to_owned(),
+            None => continue,
+        };
+        let name = match from_upstream_ref(&upstream_name) {
+            Some(n) => n,
+            None => continue,
+        };
+
+        let target = format!("refs/JOSH_REMOTE/{}", name.trim_start_matches("refs/"));
+        apply_spec_forward(repo, fm, bm, &remote.spec, &upstream_name, &target);
+
+        if let Ok(oid) = repo.revparse_single(&target).map(|o| o.id()) {
+            writeln!(stdout, "{} {}", oid, name).map_err(|_| ())?;
+        }
+    }
+    writeln!(stdout).map_err(|_| ())
+}
+
+fn handle_batch(
+    stdin: &io::Stdin,
+    stdout: &mut impl Write,
+    first_line: &str,
+    mut on_line: impl FnMut(&str) -> Result<Option<String>, ()>,
+) -> Result<(), ()> {
+    let mut line = first_line.to_owned();
+    loop {
+        if let Some(reply) = on_line(&line)? {
+            writeln!(stdout, "{}", reply).map_err(|_| ())?;
+        }
+
+        line.clear();
+        if stdin.lock().read_line(&mut line).map_err(|_| ())? == 0 {
+            return Ok(());
+        }
+        let trimmed = line.trim_end().to_owned();
+        if trimmed.is_empty() {
+            return Ok(());
+        }
+        line = trimmed;
+    }
+}
+
+/// `fetch <sha1> <name>`: runs the view forward from the ref's
+/// `refs/josh/upstream/*`-scoped copy onto a local tracking ref so git can
+/// read the filtered objects it wants.
+fn handle_fetch_line(
+    repo: &git2::Repository,
+    remote: &Remote,
+    fm: &mut view_maps::ViewMaps,
+    bm: &mut view_maps::ViewMaps,
+    line: &str,
+) -> Result<Option<String>, ()> {
+    let mut parts = line.splitn(3, ' ');
+    parts.next();
+    let _sha1 = parts.next().unwrap_or("");
+    let name = parts.next().unwrap_or("").to_owned();
+    let upstream_name = match to_upstream_ref(&name) {
+        Some(n) => n,
+        None => return Ok(None),
+    };
+
+    let target = format!("refs/JOSH_REMOTE/{}", name.trim_start_matches("refs/"));
+    apply_spec_forward(repo, fm, bm, &remote.spec, &upstream_name, &target);
+    Ok(None)
+}
+
+/// `push <src>:<dst>`: rewrites the filtered commits back onto the view's
+/// own history via `unapply_view`, writes the result locally under `dst`,
+/// then pushes that same ref on to `remote.url` before reporting the
+/// result back to git.
+fn handle_push_line(
+    repo: &git2::Repository,
+    remote: &Remote,
+    bm: &view_maps::ViewMaps,
+    line: &str,
+) -> Result<Option<String>, ()> {
+    let spec = line.trim_start_matches("push ").trim_start_matches('+');
+    let mut refspec = spec.splitn(2, ':');
+    let src = refspec.next().unwrap_or("");
+    let dst = refspec.next().unwrap_or("");
+
+    let new = match repo.revparse_single(src) {
+        Ok(o) => o.id(),
+        Err(e) => return Ok(Some(format!("error {} {}", dst, e))),
+    };
+    let old = repo
+        .revparse_single(dst)
+        .map(|o| o.id())
+        .unwrap_or_else(|_| git2::Oid::zero());
+
+    match apply_spec_reverse(repo, bm, &remote.spec, old, new) {
+        josh::UnapplyView::Done(rewritten) => {
+            if let Err(e) = repo.reference(dst, rewritten, true, "remote-helper push") {
+                return Ok(Some(format!("error {} {}", dst, e)));
+            }
+            match push_to_upstream(repo, remote, dst) {
+                Ok(()) => Ok(Some(format!("ok {}", dst))),
+                Err(()) => Ok(Some(format!("error {} failed to push to upstream", dst))),
+            }
+        }
+        _ => Ok(Some(format!("error {} rejected by view", dst))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_remote_url_splits_spec_and_url() {
+        let remote = parse_remote_url("josh:::src=foo::https://example.com/repo.git");
+        assert_eq!(remote.spec, ":src=foo");
+        assert_eq!(remote.url, "https://example.com/repo.git");
+    }
+
+    #[test]
+    fn to_upstream_ref_scopes_heads_and_tags() {
+        assert_eq!(
+            to_upstream_ref("refs/heads/main"),
+            Some("refs/josh/upstream/heads/main".to_owned())
+        );
+        assert_eq!(
+            to_upstream_ref("refs/tags/v1.0.0"),
+            Some("refs/josh/upstream/tags/v1.0.0".to_owned())
+        );
+    }
+
+    #[test]
+    fn to_upstream_ref_ignores_bookkeeping_namespaces() {
+        // Refs the helper or josh-filter itself writes (JOSH_REMOTE,
+        // JOSH_TMP, JOSH_FILTER_STATE) must never be treated as upstream
+        // branches/tags, or a second invocation would re-scope its own
+        // output and produce a doubled path.
+        assert_eq!(to_upstream_ref("refs/JOSH_REMOTE/heads/main"), None);
+        assert_eq!(to_upstream_ref("refs/JOSH_TMP"), None);
+        assert_eq!(to_upstream_ref("refs/JOSH_FILTER_STATE/heads/main"), None);
+    }
+
+    #[test]
+    fn from_upstream_ref_is_the_inverse_of_to_upstream_ref() {
+        for name in ["refs/heads/main", "refs/tags/v1.0.0", "refs/heads/a/b"] {
+            let upstream = to_upstream_ref(name).unwrap();
+            assert_eq!(from_upstream_ref(&upstream).as_deref(), Some(name));
+        }
+    }
+
+    #[test]
+    fn from_upstream_ref_ignores_unrelated_refs() {
+        assert_eq!(from_upstream_ref("refs/heads/main"), None);
+        assert_eq!(from_upstream_ref("refs/JOSH_REMOTE/heads/main"), None);
+    }
+}